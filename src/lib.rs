@@ -12,14 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::error::Error;
+use std::fmt;
 
 extern crate openssl;
+extern crate sha3;
+extern crate blake2;
 
-use openssl::hash::{hash, MessageDigest};
+mod varint;
+
+use varint::{write_varint, read_varint, VarintError};
+use openssl::hash::{hash, MessageDigest, Hasher as OpenSSLHasher};
+use sha3::Digest as Sha3DigestTrait;
+use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512, Keccak224, Keccak256, Keccak384, Keccak512, Shake128, Shake256};
+use sha3::digest::{Input, ExtendableOutput, XofReader, DynDigest};
+use blake2::{Blake2b, Blake2s};
 
 /// List of types currently supported in Multihash.
-/// SHA3, Blake2b, and Blake2s are not yet supported in OpenSSL, so are not available in rust-multihash.
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum HashTypes {
     Identity,
@@ -34,7 +42,11 @@ pub enum HashTypes {
     Shake128,
     Shake256,
     Blake2b,
-    Blake2s
+    Blake2s,
+    Keccak224,
+    Keccak256,
+    Keccak384,
+    Keccak512,
 }
 
 impl HashTypes {
@@ -50,6 +62,10 @@ impl HashTypes {
             HashTypes::SHA3224 => 0x17,
             HashTypes::Shake128 => 0x18,
             HashTypes::Shake256 => 0x19,
+            HashTypes::Keccak224 => 0x1a,
+            HashTypes::Keccak256 => 0x1b,
+            HashTypes::Keccak384 => 0x1c,
+            HashTypes::Keccak512 => 0x1d,
             HashTypes::Blake2b => 0x40,
             HashTypes::Blake2s => 0x41,
         }
@@ -68,6 +84,10 @@ impl HashTypes {
             0x17 => Some(HashTypes::SHA3224),
             0x18 => Some(HashTypes::Shake128),
             0x19 => Some(HashTypes::Shake256),
+            0x1a => Some(HashTypes::Keccak224),
+            0x1b => Some(HashTypes::Keccak256),
+            0x1c => Some(HashTypes::Keccak384),
+            0x1d => Some(HashTypes::Keccak512),
             0x40 => Some(HashTypes::Blake2b),
             0x41 => Some(HashTypes::Blake2s),
             _ => None
@@ -75,6 +95,60 @@ impl HashTypes {
     }
 }
 
+/// The error type returned by this crate's encode/decode APIs.
+#[derive(Debug)]
+pub enum Error {
+    /// The requested [`HashTypes`] isn't implemented by this crate (yet).
+    UnsupportedHash(HashTypes),
+    /// The requested digest length doesn't fit within what the backend can produce.
+    InputTooLong,
+    /// `decode` saw a leading code that doesn't match any known [`HashTypes`].
+    UnknownCode(u64),
+    /// The buffer is shorter than the length it declares, or ends before its
+    /// code or length varint is complete.
+    Truncated,
+    /// A code or length varint is malformed (e.g. doesn't fit in a `u64`).
+    MalformedVarint,
+    /// The buffer has extra bytes beyond the length it declares.
+    TrailingData,
+    /// The OpenSSL backend returned an error.
+    Backend(openssl::error::ErrorStack),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnsupportedHash(hash_type) => write!(f, "we don't support the {:?} hash algorithm yet", hash_type),
+            Error::InputTooLong => write!(f, "requested digest length is too long for this hash algorithm"),
+            Error::UnknownCode(code) => write!(f, "unrecognized multihash code {:#x}", code),
+            Error::Truncated => write!(f, "buffer is shorter than its declared digest length"),
+            Error::MalformedVarint => write!(f, "code or length varint is malformed"),
+            Error::TrailingData => write!(f, "buffer has trailing data beyond its declared digest length"),
+            Error::Backend(ref e) => write!(f, "hash backend error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Backend(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(e: openssl::error::ErrorStack) -> Error {
+        Error::Backend(e)
+    }
+}
+
+/// The default digest length (in bytes) used for extendable-output functions
+/// (SHAKE128/256) when no explicit output length is requested.
+const SHAKE128_DEFAULT_LEN: usize = 32;
+const SHAKE256_DEFAULT_LEN: usize = 64;
+
 /// Hashes the input using the given hash algorithm. Also adds the leading bytes for type of algo
 /// and length of digest.
 ///
@@ -85,52 +159,255 @@ impl HashTypes {
 /// let testphrase = b"Hello World";
 /// let digest = multihash(HashTypes::SHA2512, testphrase.to_vec());
 /// ```
-pub fn multihash(wanthash: HashTypes, input: Vec<u8>) -> Result<Vec<u8>, String> {
-    enum PrivHashType {
-        OpenSSL(MessageDigest),
-        Identity,
-        None
-    };
+pub fn multihash(wanthash: HashTypes, input: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let digest = raw_digest(wanthash, input)?;
+    Ok(with_multihash_prefix(wanthash.to_u8(), digest))
+}
 
-    let ssl_hash: PrivHashType = match wanthash {
-        HashTypes::Identity => PrivHashType::Identity,
-        HashTypes::SHA1 => PrivHashType::OpenSSL(MessageDigest::sha1()),
-        HashTypes::SHA2256 => PrivHashType::OpenSSL(MessageDigest::sha256()),
-        HashTypes::SHA2512 => PrivHashType::OpenSSL(MessageDigest::sha512()),
-        _ => PrivHashType::None,
-    };
-    match ssl_hash {
-        PrivHashType::OpenSSL(openssl_type) => {
-            let mut temphash = hash(openssl_type, input.as_slice()).map_err(|e| e.description().to_owned())?;
-            let length = temphash.len() as u8;
-            temphash.insert(0, length);
-            temphash.insert(0, wanthash.to_u8()); // Add the hashtype to the hash.
-            Ok(temphash)
+/// Like [`multihash`], but lets the caller request a specific digest length.
+///
+/// For extendable-output functions (`Shake128`/`Shake256`) this squeezes exactly
+/// `out_len` bytes. For fixed-output hashes (including `Blake2b`/`Blake2s`, which
+/// naturally produce 64/32 bytes) it truncates the natural digest to `out_len`
+/// bytes, which is rejected if `out_len` is longer than the hash naturally
+/// produces. The emitted length prefix always reflects the actual produced
+/// length, so the result still round-trips through [`decode`].
+pub fn multihash_with_len(wanthash: HashTypes, input: Vec<u8>, out_len: usize) -> Result<Vec<u8>, Error> {
+    let digest = match wanthash {
+        HashTypes::Shake128 => squeeze_shake128(input.as_slice(), out_len),
+        HashTypes::Shake256 => squeeze_shake256(input.as_slice(), out_len),
+        other => {
+            let natural = raw_digest(other, input)?;
+            if out_len > natural.len() {
+                return Err(Error::InputTooLong);
+            }
+            natural[..out_len].to_vec()
         }
-        PrivHashType::Identity => {
-            let in_len = input.len();
-            let mut input = input;
-            if input.len() > 255 {
-                Err("Sorry, input is too long to support the identity hash".to_owned())
-            } else {
-                input.insert(0, in_len as u8);
-                input.insert(0, wanthash.to_u8());
-                Ok(input)
+    };
+    Ok(with_multihash_prefix(wanthash.to_u8(), digest))
+}
+
+/// Computes the raw (un-prefixed) digest bytes for `wanthash` at its natural length.
+fn raw_digest(wanthash: HashTypes, input: Vec<u8>) -> Result<Vec<u8>, Error> {
+    match wanthash {
+        HashTypes::Identity => Ok(input),
+        HashTypes::SHA1 => Ok(hash(MessageDigest::sha1(), input.as_slice())?.to_vec()),
+        HashTypes::SHA2256 => Ok(hash(MessageDigest::sha256(), input.as_slice())?.to_vec()),
+        HashTypes::SHA2512 => Ok(hash(MessageDigest::sha512(), input.as_slice())?.to_vec()),
+        other => rust_crypto_digest(other, input.as_slice()),
+    }
+}
+
+/// Builds a full multihash buffer by prepending the varint-encoded code and
+/// varint-encoded digest length to `digest`.
+fn with_multihash_prefix(code: u8, digest: Vec<u8>) -> Vec<u8> {
+    let mut buf = write_varint(code as u64);
+    buf.extend(write_varint(digest.len() as u64));
+    buf.extend(digest);
+    buf
+}
+
+/// A streaming/incremental hasher: feed it input in chunks with [`Hasher::update`]
+/// and get the finished multihash buffer out of [`Hasher::finalize`], without
+/// needing to hold the whole input in memory at once.
+///
+/// # Example
+/// ```
+/// use rust_multihash::{HashTypes, Hasher, multihash};
+///
+/// let mut h = Hasher::new(HashTypes::SHA2256).unwrap();
+/// h.update(b"Hello ").unwrap();
+/// h.update(b"World").unwrap();
+/// let mh = h.finalize().unwrap();
+///
+/// assert_eq!(mh, multihash(HashTypes::SHA2256, b"Hello World".to_vec()).unwrap());
+/// ```
+pub struct Hasher {
+    wanthash: HashTypes,
+    inner: HasherInner,
+}
+
+enum HasherInner {
+    OpenSSL(OpenSSLHasher),
+    RustCrypto(Box<dyn DynDigest>),
+    Shake128(Shake128),
+    Shake256(Shake256),
+    Identity(Vec<u8>),
+}
+
+impl Hasher {
+    pub fn new(wanthash: HashTypes) -> Result<Hasher, Error> {
+        let inner = match wanthash {
+            HashTypes::Identity => HasherInner::Identity(Vec::new()),
+            HashTypes::SHA1 => HasherInner::OpenSSL(OpenSSLHasher::new(MessageDigest::sha1())?),
+            HashTypes::SHA2256 => HasherInner::OpenSSL(OpenSSLHasher::new(MessageDigest::sha256())?),
+            HashTypes::SHA2512 => HasherInner::OpenSSL(OpenSSLHasher::new(MessageDigest::sha512())?),
+            HashTypes::Shake128 => HasherInner::Shake128(Shake128::default()),
+            HashTypes::Shake256 => HasherInner::Shake256(Shake256::default()),
+            HashTypes::SHA3 | HashTypes::SHA3512 => HasherInner::RustCrypto(Box::new(Sha3_512::default())),
+            HashTypes::SHA3384 => HasherInner::RustCrypto(Box::new(Sha3_384::default())),
+            HashTypes::SHA3256 => HasherInner::RustCrypto(Box::new(Sha3_256::default())),
+            HashTypes::SHA3224 => HasherInner::RustCrypto(Box::new(Sha3_224::default())),
+            HashTypes::Keccak224 => HasherInner::RustCrypto(Box::new(Keccak224::default())),
+            HashTypes::Keccak256 => HasherInner::RustCrypto(Box::new(Keccak256::default())),
+            HashTypes::Keccak384 => HasherInner::RustCrypto(Box::new(Keccak384::default())),
+            HashTypes::Keccak512 => HasherInner::RustCrypto(Box::new(Keccak512::default())),
+            HashTypes::Blake2b => HasherInner::RustCrypto(Box::new(Blake2b::default())),
+            HashTypes::Blake2s => HasherInner::RustCrypto(Box::new(Blake2s::default())),
+        };
+        Ok(Hasher { wanthash, inner })
+    }
+
+    /// Feeds another chunk of input into the hasher. May be called any number of times.
+    pub fn update(&mut self, data: &[u8]) -> Result<(), Error> {
+        match self.inner {
+            HasherInner::OpenSSL(ref mut h) => Ok(h.update(data)?),
+            HasherInner::RustCrypto(ref mut h) => {
+                h.input(data);
+                Ok(())
+            }
+            HasherInner::Shake128(ref mut h) => {
+                h.input(data);
+                Ok(())
+            }
+            HasherInner::Shake256(ref mut h) => {
+                h.input(data);
+                Ok(())
+            }
+            HasherInner::Identity(ref mut buf) => {
+                buf.extend_from_slice(data);
+                Ok(())
             }
         }
-        PrivHashType::None => Err("Sorry, we don't support that hash algorithm yet.".to_string()),
+    }
+
+    /// Consumes the hasher and produces the finished, type/length-prefixed multihash buffer.
+    pub fn finalize(self) -> Result<Vec<u8>, Error> {
+        let digest = match self.inner {
+            HasherInner::OpenSSL(mut h) => h.finish()?.to_vec(),
+            HasherInner::RustCrypto(mut h) => h.result_reset().to_vec(),
+            HasherInner::Shake128(h) => {
+                let mut out = vec![0u8; SHAKE128_DEFAULT_LEN];
+                h.xof_result().read(&mut out);
+                out
+            }
+            HasherInner::Shake256(h) => {
+                let mut out = vec![0u8; SHAKE256_DEFAULT_LEN];
+                h.xof_result().read(&mut out);
+                out
+            }
+            HasherInner::Identity(buf) => buf,
+        };
+        Ok(with_multihash_prefix(self.wanthash.to_u8(), digest))
+    }
+}
+
+/// The parsed parts of a multihash buffer, as returned by [`decode`].
+#[derive(PartialEq, Clone, Debug)]
+pub struct DecodedMultihash {
+    pub hash_type: HashTypes,
+    pub length: usize,
+    pub digest: Vec<u8>,
+}
+
+/// Parses a multihash buffer back into its parts: the [`HashTypes`], the declared
+/// digest length, and the raw digest bytes.
+///
+/// # Example
+/// ```
+/// use rust_multihash::{HashTypes, multihash, decode};
+///
+/// let testphrase = b"Hello World";
+/// let buf = multihash(HashTypes::SHA2512, testphrase.to_vec()).unwrap();
+/// let decoded = decode(&buf).unwrap();
+/// assert_eq!(decoded.hash_type, HashTypes::SHA2512);
+/// ```
+pub fn decode(buf: &[u8]) -> Result<DecodedMultihash, Error> {
+    let (code, code_len) = read_varint(buf).map_err(from_varint_error)?;
+    if code > u8::MAX as u64 {
+        return Err(Error::UnknownCode(code));
+    }
+    let hash_type = HashTypes::from_u8(code as u8)
+        .ok_or(Error::UnknownCode(code))?;
+
+    let (length, length_len) = read_varint(&buf[code_len..]).map_err(from_varint_error)?;
+    let length = length as usize;
+
+    let digest = &buf[code_len + length_len..];
+    if digest.len() < length {
+        return Err(Error::Truncated);
+    }
+    if digest.len() > length {
+        return Err(Error::TrailingData);
+    }
+
+    Ok(DecodedMultihash {
+        hash_type,
+        length,
+        digest: digest.to_vec(),
+    })
+}
+
+/// Maps a [`VarintError`] onto the corresponding [`Error`] variant.
+fn from_varint_error(e: VarintError) -> Error {
+    match e {
+        VarintError::Truncated => Error::Truncated,
+        VarintError::Overflow => Error::MalformedVarint,
+    }
+}
+
+/// Computes a digest using one of the pure-Rust (RustCrypto) backends.
+fn rust_crypto_digest(wanthash: HashTypes, input: &[u8]) -> Result<Vec<u8>, Error> {
+    match wanthash {
+        HashTypes::SHA3 | HashTypes::SHA3512 => Ok(Sha3_512::digest(input).to_vec()),
+        HashTypes::SHA3384 => Ok(Sha3_384::digest(input).to_vec()),
+        HashTypes::SHA3256 => Ok(Sha3_256::digest(input).to_vec()),
+        HashTypes::SHA3224 => Ok(Sha3_224::digest(input).to_vec()),
+        HashTypes::Keccak224 => Ok(Keccak224::digest(input).to_vec()),
+        HashTypes::Keccak256 => Ok(Keccak256::digest(input).to_vec()),
+        HashTypes::Keccak384 => Ok(Keccak384::digest(input).to_vec()),
+        HashTypes::Keccak512 => Ok(Keccak512::digest(input).to_vec()),
+        HashTypes::Shake128 => Ok(squeeze_shake128(input, SHAKE128_DEFAULT_LEN)),
+        HashTypes::Shake256 => Ok(squeeze_shake256(input, SHAKE256_DEFAULT_LEN)),
+        HashTypes::Blake2b => Ok(Blake2b::digest(input).to_vec()),
+        HashTypes::Blake2s => Ok(Blake2s::digest(input).to_vec()),
+        other => Err(Error::UnsupportedHash(other)),
     }
 }
 
+/// Squeezes exactly `out_len` bytes of SHAKE128 output for `input`.
+fn squeeze_shake128(input: &[u8], out_len: usize) -> Vec<u8> {
+    let mut hasher = Shake128::default();
+    hasher.input(input);
+    let mut out = vec![0u8; out_len];
+    hasher.xof_result().read(&mut out);
+    out
+}
+
+/// Squeezes exactly `out_len` bytes of SHAKE256 output for `input`.
+fn squeeze_shake256(input: &[u8], out_len: usize) -> Vec<u8> {
+    let mut hasher = Shake256::default();
+    hasher.input(input);
+    let mut out = vec![0u8; out_len];
+    hasher.xof_result().read(&mut out);
+    out
+}
+
 #[cfg(test)]
 mod test {
-    use super::{HashTypes, multihash};
+    use super::{HashTypes, multihash, multihash_with_len, decode, Hasher, Error};
     use openssl::hash::{hash, MessageDigest};
 
+    /// Renders `bytes` as a lowercase hex string, for comparing digests against
+    /// published test vectors.
+    fn hex_encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
     #[test]
     fn test1() {
         let example = b"hello world";
-        let mut result = hash(MessageDigest::sha256(), example).unwrap();
+        let mut result = hash(MessageDigest::sha256(), example).unwrap().to_vec();
         let length = result.len() as u8;
         result.insert(0, 0x12);
         result.insert(1, length);
@@ -146,4 +423,286 @@ mod test {
     fn test_id() {
         assert_eq!(multihash(HashTypes::Identity, b"hello".to_vec()).unwrap(), b"\x00\x05hello");
     }
+
+    #[test]
+    fn test_id_longer_than_255_bytes() {
+        // The old single-byte length prefix capped the identity hash at 255
+        // bytes; the varint length prefix has no such ceiling.
+        let input = vec![0x42u8; 300];
+        let buf = multihash(HashTypes::Identity, input.clone()).unwrap();
+        let decoded = decode(&buf).unwrap();
+        assert_eq!(decoded.hash_type, HashTypes::Identity);
+        assert_eq!(decoded.length, 300);
+        assert_eq!(decoded.digest, input);
+    }
+
+    #[test]
+    fn test_sha3_512() {
+        use sha3::{Digest, Sha3_512};
+        let example = b"hello world";
+        let mut result = Sha3_512::digest(example).to_vec();
+        // NIST FIPS 202 SHA3-512("hello world")
+        assert_eq!(hex_encode(&result), "840006653e9ac9e95117a15c915caab81662918e925de9e004f774ff82d7079a40d4d27b1b372657c61d46d470304c88c788b3a4527ad074d1dccbee5dbaa99a");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x14);
+
+        assert_eq!(multihash(HashTypes::SHA3512, example.to_vec()).unwrap(), result);
+        assert_eq!(multihash(HashTypes::SHA3, example.to_vec()).unwrap(), result);
+    }
+
+    #[test]
+    fn test_sha3_256() {
+        use sha3::{Digest, Sha3_256};
+        let example = b"hello world";
+        let mut result = Sha3_256::digest(example).to_vec();
+        // NIST FIPS 202 SHA3-256("hello world")
+        assert_eq!(hex_encode(&result), "644bcc7e564373040999aac89e7622f3ca71fba1d972fd94a31c3bfbf24e3938");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x16);
+
+        assert_eq!(multihash(HashTypes::SHA3256, example.to_vec()).unwrap(), result);
+    }
+
+    #[test]
+    fn test_keccak_256() {
+        use sha3::{Digest, Keccak256};
+        // The original Keccak padding (as opposed to NIST's final SHA3 padding)
+        // has a well-known published digest for the empty string.
+        let example = b"";
+        let mut result = Keccak256::digest(example).to_vec();
+        assert_eq!(hex_encode(&result), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x1b);
+
+        assert_eq!(multihash(HashTypes::Keccak256, example.to_vec()).unwrap(), result);
+        assert_eq!(HashTypes::from_u8(0x1b), Some(HashTypes::Keccak256));
+    }
+
+    #[test]
+    fn test_blake2b() {
+        use blake2::{Blake2b, Digest};
+        let example = b"hello world";
+        let mut result = Blake2b::digest(example).to_vec();
+        // RFC 7693 BLAKE2b-512("hello world")
+        assert_eq!(hex_encode(&result), "021ced8799296ceca557832ab941a50b4a11f83478cf141f51f933f653ab9fbcc05a037cddbed06e309bf334942c4e58cdf1a46e237911ccd7fcf9787cbc7fd0");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x40);
+
+        assert_eq!(multihash(HashTypes::Blake2b, example.to_vec()).unwrap(), result);
+    }
+
+    #[test]
+    fn test_blake2s() {
+        use blake2::{Blake2s, Digest};
+        let example = b"hello world";
+        let mut result = Blake2s::digest(example).to_vec();
+        // RFC 7693 BLAKE2s-256("hello world")
+        assert_eq!(hex_encode(&result), "9aec6806794561107e594b1f6a8a6b0c92a0cba9acf5e5e93cca06f781813b0b");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x41);
+
+        assert_eq!(multihash(HashTypes::Blake2s, example.to_vec()).unwrap(), result);
+    }
+
+    #[test]
+    fn test_shake128() {
+        use sha3::Shake128;
+        use sha3::digest::{Input, ExtendableOutput, XofReader};
+        let example = b"hello world";
+        let mut hasher = Shake128::default();
+        hasher.input(example);
+        let mut result = vec![0u8; super::SHAKE128_DEFAULT_LEN];
+        hasher.xof_result().read(&mut result);
+        // NIST FIPS 202 SHAKE128("hello world", 32 bytes)
+        assert_eq!(hex_encode(&result), "3a9159f071e4dd1c8c4f968607c30942e120d8156b8b1e72e0d376e8871cb8b8");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x18);
+
+        assert_eq!(multihash(HashTypes::Shake128, example.to_vec()).unwrap(), result);
+    }
+
+    #[test]
+    fn test_shake256() {
+        use sha3::Shake256;
+        use sha3::digest::{Input, ExtendableOutput, XofReader};
+        let example = b"hello world";
+        let mut hasher = Shake256::default();
+        hasher.input(example);
+        let mut result = vec![0u8; super::SHAKE256_DEFAULT_LEN];
+        hasher.xof_result().read(&mut result);
+        // NIST FIPS 202 SHAKE256("hello world", 64 bytes)
+        assert_eq!(hex_encode(&result), "369771bb2cb9d2b04c1d54cca487e372d9f187f73f7ba3f65b95c8ee7798c527f4f3c2d55c2d46a29f2e945d469c3df27853a8735271f5cc2d9e889544357116");
+        let length = result.len() as u8;
+        result.insert(0, length);
+        result.insert(0, 0x19);
+
+        assert_eq!(multihash(HashTypes::Shake256, example.to_vec()).unwrap(), result);
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let example = b"hello world";
+        let buf = multihash(HashTypes::SHA2256, example.to_vec()).unwrap();
+        let decoded = decode(&buf).unwrap();
+        assert_eq!(decoded.hash_type, HashTypes::SHA2256);
+        assert_eq!(decoded.length, 32);
+        assert_eq!(decoded.digest, buf[2..].to_vec());
+    }
+
+    #[test]
+    fn test_decode_unknown_code() {
+        let buf = [0xfe, 0x02, 0xaa, 0xbb];
+        assert!(decode(&buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let buf = [0x12, 0x20, 0xaa, 0xbb]; // claims 32 bytes, only has 2
+        assert!(decode(&buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_trailing_garbage() {
+        let example = b"hello world";
+        let mut buf = multihash(HashTypes::SHA2256, example.to_vec()).unwrap();
+        buf.push(0xff);
+        assert!(decode(&buf).is_err());
+    }
+
+    #[test]
+    fn test_hasher_matches_one_shot_openssl() {
+        let mut h = Hasher::new(HashTypes::SHA2256).unwrap();
+        h.update(b"hello ").unwrap();
+        h.update(b"world").unwrap();
+        let streamed = h.finalize().unwrap();
+
+        let oneshot = multihash(HashTypes::SHA2256, b"hello world".to_vec()).unwrap();
+        assert_eq!(streamed, oneshot);
+    }
+
+    #[test]
+    fn test_hasher_matches_one_shot_rust_crypto() {
+        let mut h = Hasher::new(HashTypes::Blake2b).unwrap();
+        h.update(b"hello ").unwrap();
+        h.update(b"world").unwrap();
+        let streamed = h.finalize().unwrap();
+
+        let oneshot = multihash(HashTypes::Blake2b, b"hello world".to_vec()).unwrap();
+        assert_eq!(streamed, oneshot);
+    }
+
+    #[test]
+    fn test_hasher_matches_one_shot_shake() {
+        let mut h = Hasher::new(HashTypes::Shake128).unwrap();
+        h.update(b"hello ").unwrap();
+        h.update(b"world").unwrap();
+        let streamed = h.finalize().unwrap();
+
+        let oneshot = multihash(HashTypes::Shake128, b"hello world".to_vec()).unwrap();
+        assert_eq!(streamed, oneshot);
+    }
+
+    #[test]
+    fn test_hasher_identity() {
+        let mut h = Hasher::new(HashTypes::Identity).unwrap();
+        h.update(b"hello").unwrap();
+        let streamed = h.finalize().unwrap();
+        assert_eq!(streamed, b"\x00\x05hello");
+    }
+
+    #[test]
+    fn test_decode_unknown_code_is_typed() {
+        // 0x7e has its high bit clear, so this is a genuine single-byte code.
+        let buf = [0x7e, 0x02, 0xaa, 0xbb];
+        match decode(&buf) {
+            Err(Error::UnknownCode(0x7e)) => {}
+            other => panic!("expected Error::UnknownCode(0x7e), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_unknown_code_over_u8_is_not_truncated() {
+        // code 300 as a varint is [0xac, 0x02]; if the error truncated the code
+        // to a u8 it would misreport this as code 44 instead of 300.
+        let buf = [0xac, 0x02, 0x00];
+        match decode(&buf) {
+            Err(Error::UnknownCode(300)) => {}
+            other => panic!("expected Error::UnknownCode(300), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated_is_typed() {
+        let buf = [0x12, 0x20, 0xaa, 0xbb];
+        match decode(&buf) {
+            Err(Error::Truncated) => {}
+            other => panic!("expected Error::Truncated, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_trailing_data_is_typed() {
+        let example = b"hello world";
+        let mut buf = multihash(HashTypes::SHA2256, example.to_vec()).unwrap();
+        buf.push(0xff);
+        match decode(&buf) {
+            Err(Error::TrailingData) => {}
+            other => panic!("expected Error::TrailingData, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_with_len_shake128_squeezes_requested_length() {
+        let example = b"hello world";
+        let buf = multihash_with_len(HashTypes::Shake128, example.to_vec(), 10).unwrap();
+        let decoded = decode(&buf).unwrap();
+        assert_eq!(decoded.hash_type, HashTypes::Shake128);
+        assert_eq!(decoded.length, 10);
+        assert_eq!(decoded.digest.len(), 10);
+    }
+
+    #[test]
+    fn test_with_len_shake256_longer_than_default() {
+        let example = b"hello world";
+        let buf = multihash_with_len(HashTypes::Shake256, example.to_vec(), 128).unwrap();
+        let decoded = decode(&buf).unwrap();
+        assert_eq!(decoded.length, 128);
+    }
+
+    #[test]
+    fn test_with_len_truncates_fixed_hash() {
+        let example = b"hello world";
+        let full = multihash(HashTypes::Blake2b, example.to_vec()).unwrap();
+        let truncated = multihash_with_len(HashTypes::Blake2b, example.to_vec(), 20).unwrap();
+        let decoded = decode(&truncated).unwrap();
+        assert_eq!(decoded.length, 20);
+        // the truncated digest is a prefix of the natural (64-byte) digest.
+        assert_eq!(decoded.digest, full[full.len() - 64..full.len() - 64 + 20]);
+    }
+
+    #[test]
+    fn test_with_len_rejects_length_longer_than_natural() {
+        let example = b"hello world";
+        match multihash_with_len(HashTypes::SHA2256, example.to_vec(), 1000) {
+            Err(Error::InputTooLong) => {}
+            other => panic!("expected Error::InputTooLong, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed_varint_is_not_truncated() {
+        // Eleven continuation-bit bytes: too long to be a valid varint, as
+        // opposed to a buffer that simply ran out of bytes.
+        let buf = [0x80u8; 11];
+        match decode(&buf) {
+            Err(Error::MalformedVarint) => {}
+            other => panic!("expected Error::MalformedVarint, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,97 @@
+//! Minimal unsigned varint (LEB128) encoding, as used by the multihash spec
+//! for the code and length prefixes.
+
+/// The ways reading a varint can fail, distinct from each other so callers
+/// can tell "need more bytes" apart from "this prefix is malformed".
+#[derive(Debug, PartialEq)]
+pub enum VarintError {
+    /// `buf` ran out before a terminating (high-bit-clear) byte was found.
+    Truncated,
+    /// The encoded value doesn't fit in a `u64`.
+    Overflow,
+}
+
+/// Encodes `value` as an unsigned varint: 7 bits of value per byte, with the
+/// high bit set on every byte except the last.
+pub fn write_varint(mut value: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+    out
+}
+
+/// Reads an unsigned varint from the front of `buf`, returning the decoded
+/// value and the number of bytes consumed.
+///
+/// Errors if `buf` runs out before a terminating byte is found, or if the
+/// value would overflow a `u64`.
+pub fn read_varint(buf: &[u8]) -> Result<(u64, usize), VarintError> {
+    let mut value: u64 = 0;
+    for (i, &byte) in buf.iter().enumerate() {
+        if i >= 10 {
+            return Err(VarintError::Overflow);
+        }
+        let low_bits = (byte & 0x7f) as u64;
+        let shift = i as u32 * 7;
+        if shift >= 64 || (low_bits.checked_shl(shift).unwrap_or(u64::MAX) >> shift) != low_bits {
+            return Err(VarintError::Overflow);
+        }
+        value |= low_bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(VarintError::Truncated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_varint, read_varint, VarintError};
+
+    #[test]
+    fn test_roundtrip_small() {
+        for v in 0..300u64 {
+            let buf = write_varint(v);
+            let (decoded, used) = read_varint(&buf).unwrap();
+            assert_eq!(decoded, v);
+            assert_eq!(used, buf.len());
+        }
+    }
+
+    #[test]
+    fn test_known_encodings() {
+        assert_eq!(write_varint(0x00), vec![0x00]);
+        assert_eq!(write_varint(0x7f), vec![0x7f]);
+        assert_eq!(write_varint(0x80), vec![0x80, 0x01]);
+        assert_eq!(write_varint(300), vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(read_varint(&[0x80]), Err(VarintError::Truncated));
+    }
+
+    #[test]
+    fn test_overflow_distinct_from_truncated() {
+        // Eleven continuation bytes: too long to ever fit in a u64, which is
+        // a different failure than simply running out of buffer.
+        let buf = [0x80u8; 11];
+        assert_eq!(read_varint(&buf), Err(VarintError::Overflow));
+    }
+
+    #[test]
+    fn test_trailing_bytes_ignored_past_value() {
+        let (value, used) = read_varint(&[0x01, 0xff, 0xff]).unwrap();
+        assert_eq!(value, 1);
+        assert_eq!(used, 1);
+    }
+}